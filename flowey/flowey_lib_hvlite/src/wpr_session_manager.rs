@@ -1,9 +1,10 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-//! Node for managing WPR tracing sessions during test execution.
+//! Node for managing tracing sessions during test execution.
 
-use crate::wpr_tracing::WprSession;
+use crate::wpr_tracing::get_trace_backend_from_env;
+use crate::wpr_tracing::open_trace_session;
 use flowey::node::prelude::*;
 use std::collections::BTreeMap;
 
@@ -15,6 +16,10 @@ flowey_request! {
         pub extra_env: ReadVar<BTreeMap<String, String>>,
         /// Pre-run dependencies
         pub pre_run_deps: Vec<ReadVar<SideEffect>>,
+        /// Whether the test the session was tracing failed. Read after the
+        /// test body has run, so the session can keep the trace on failure
+        /// and discard it (instead of flushing a full ETL) on success.
+        pub test_failed: ReadVar<bool>,
         /// WPR trace file output (if tracing was enabled and successful)
         pub trace_file: Option<WriteVar<Option<PathBuf>>>,
         /// Side effect indicating WPR session is ready
@@ -34,6 +39,7 @@ impl FlowNode for Node {
             session_name,
             extra_env,
             pre_run_deps,
+            test_failed,
             trace_file,
             wpr_ready,
         } in requests
@@ -42,6 +48,7 @@ impl FlowNode for Node {
                 let session_name = session_name.clone();
                 let extra_env = extra_env.claim(ctx);
                 let pre_run_deps = pre_run_deps.claim(ctx);
+                let test_failed = test_failed.claim(ctx);
                 let trace_file = trace_file.claim(ctx);
                 let wpr_ready = wpr_ready.claim(ctx);
 
@@ -51,6 +58,10 @@ impl FlowNode for Node {
                         rt.read(dep);
                     }
 
+                    // Read once the test body has finished, so we know
+                    // whether to keep the trace or discard it.
+                    let test_failed = rt.read(test_failed);
+
                     // Read environment variables to get WPR configuration
                     let env = rt.read(extra_env);
                     
@@ -77,43 +88,49 @@ impl FlowNode for Node {
                             .map(PathBuf::from)
                             .unwrap_or_else(|| std::env::temp_dir().join("openvmm_wpr_traces"));
 
+                        let wpr_mode = match env.get("OPENVMM_WPR_MODE").map(String::as_str) {
+                            Some("memory") => crate::wpr_tracing::WprLoggingMode::Memory,
+                            _ => crate::wpr_tracing::WprLoggingMode::File,
+                        };
+
                         let config = crate::wpr_tracing::WprConfig {
                             enabled: true,
                             profile: wpr_profile,
                             output_dir: wpr_output_dir,
+                            mode: wpr_mode,
                         };
-                        
-                        // Create and start WPR session
-                        let session = WprSession::new(session_name, config);
-                        match session.start() {
-                            Ok(()) => {
-                                log::info!("WPR session started successfully");
-                                
-                                // Stop the session and get the trace file
-                                let trace_result = session.stop();
-                                match trace_result {
-                                    Ok(Some(etl_file)) => {
-                                        log::info!("WPR trace saved to: {}", etl_file.display());
-                                        if let Some(trace_file) = trace_file {
-                                            rt.write(trace_file, &Some(etl_file));
-                                        }
-                                    }
-                                    Ok(None) => {
-                                        log::warn!("WPR session stopped but no trace file generated");
-                                        if let Some(trace_file) = trace_file {
-                                            rt.write(trace_file, &None);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        log::error!("Failed to stop WPR session: {}", e);
-                                        if let Some(trace_file) = trace_file {
-                                            rt.write(trace_file, &None);
-                                        }
-                                    }
+
+                        // Create and start the tracing session for whichever
+                        // backend is active on this host; the caller doesn't
+                        // need to branch on platform to do it.
+                        // In `File` mode, the trace writes to disk for the
+                        // whole run regardless of outcome, same as before
+                        // this mode existed. Only `Memory` mode's point is
+                        // to skip flushing a passing run's buffer.
+                        let keep = match wpr_mode {
+                            crate::wpr_tracing::WprLoggingMode::Memory => test_failed,
+                            crate::wpr_tracing::WprLoggingMode::File => true,
+                        };
+
+                        let backend = get_trace_backend_from_env();
+                        let session = open_trace_session(session_name, backend, config);
+                        let result = session.start().and_then(|()| session.stop_if(keep));
+
+                        match result {
+                            Ok(Some(trace)) => {
+                                log::info!("Trace saved to: {}", trace.display());
+                                if let Some(trace_file) = trace_file {
+                                    rt.write(trace_file, &Some(trace));
+                                }
+                            }
+                            Ok(None) => {
+                                log::warn!("Tracing session stopped but no trace file generated");
+                                if let Some(trace_file) = trace_file {
+                                    rt.write(trace_file, &None);
                                 }
                             }
                             Err(e) => {
-                                log::error!("Failed to start WPR session: {}", e);
+                                log::error!("Failed to run tracing session: {}", e);
                                 if let Some(trace_file) = trace_file {
                                     rt.write(trace_file, &None);
                                 }
@@ -3,13 +3,70 @@
 
 //! Windows Performance Record (WPR) tracing support for VMM tests.
 //!
-//! This module provides functionality to start/stop WPR trace sessions
-//! during test execution and collect the resulting ETL files as test artifacts.
+//! This module provides functionality to start/stop trace sessions during
+//! test execution and collect the resulting trace files as test artifacts.
+//! On Windows this is WPR/ETW; on Linux it's a kernel trace session whose
+//! output is shaped like a Perfetto/`perf.data` artifact, so the same test
+//! job produces a trace regardless of which host the guest was booted on.
 
 use flowey::node::prelude::*;
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+/// Which tracing backend a test run should use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceBackend {
+    /// Windows ETW/ETL capture via `wpr.exe`.
+    Wpr,
+    /// Linux system-wide `perf record` capture (sampled call stacks) into a
+    /// `perf.data`-shaped artifact.
+    LinuxKernelTrace,
+}
+
+impl TraceBackend {
+    /// Picks the backend for the host `cfg!` is compiled for.
+    pub fn detect() -> Self {
+        if cfg!(windows) {
+            TraceBackend::Wpr
+        } else {
+            TraceBackend::LinuxKernelTrace
+        }
+    }
+}
+
+/// A tracing session that can be started before a test body runs and
+/// stopped afterwards to collect a trace artifact, regardless of which
+/// backend is actually doing the collection.
+pub trait TraceSession {
+    /// Start the trace session.
+    fn start(&self) -> anyhow::Result<()>;
+    /// Stop the trace session, returning the collected trace file, if any.
+    /// Equivalent to `stop_if(true)`.
+    fn stop(&self) -> anyhow::Result<Option<PathBuf>> {
+        self.stop_if(true)
+    }
+    /// Stop the trace session. If `keep` is true, materializes the trace
+    /// file (flushing a [`WprLoggingMode::Memory`] buffer to disk, if
+    /// applicable); otherwise the session is torn down and no trace file is
+    /// produced. In [`WprLoggingMode::Memory`], callers pass `keep` based on
+    /// whether the test failed, so CI only keeps those traces for failing
+    /// runs; in [`WprLoggingMode::File`], they always pass `true`.
+    fn stop_if(&self, keep: bool) -> anyhow::Result<Option<PathBuf>>;
+}
+
+/// Open a trace session for `backend`, without the caller needing to branch
+/// on platform.
+pub fn open_trace_session(
+    session_name: String,
+    backend: TraceBackend,
+    config: WprConfig,
+) -> Box<dyn TraceSession> {
+    match backend {
+        TraceBackend::Wpr => Box::new(WprSession::new(session_name, config)),
+        TraceBackend::LinuxKernelTrace => Box::new(PerfSession::new(session_name, config)),
+    }
+}
+
 /// Configuration for WPR tracing
 #[derive(Debug, Clone)]
 pub struct WprConfig {
@@ -19,6 +76,9 @@ pub struct WprConfig {
     pub profile: WprProfile,
     /// Output directory for ETL files
     pub output_dir: PathBuf,
+    /// Whether to write the ETL to disk continuously, or buffer it in
+    /// memory and only materialize it when the caller signals failure
+    pub mode: WprLoggingMode,
 }
 
 /// WPR profile configuration
@@ -30,21 +90,37 @@ pub enum WprProfile {
     Custom(PathBuf),
 }
 
+/// How a WPR session buffers its trace data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WprLoggingMode {
+    /// Write the ETL to disk for the whole run
+    File,
+    /// Buffer in a circular in-memory buffer, and only write the ETL out
+    /// when the caller signals the run failed
+    Memory,
+}
+
 /// WPR session management
 pub struct WprSession {
     session_name: String,
     output_file: PathBuf,
     config: WprConfig,
+    wpr_path: PathBuf,
 }
 
 impl WprSession {
     /// Create a new WPR session
     pub fn new(session_name: String, config: WprConfig) -> Self {
         let output_file = config.output_dir.join(format!("{}.etl", session_name));
+        let wpr_path = resolve_wpr_path().unwrap_or_else(|e| {
+            log::warn!("{e:#}, falling back to `wpr` on PATH");
+            PathBuf::from("wpr")
+        });
         Self {
             session_name,
             output_file,
             config,
+            wpr_path,
         }
     }
 
@@ -74,15 +150,19 @@ impl WprSession {
         };
 
         log::info!("Starting WPR session: {}", self.session_name);
-        
-        let output = std::process::Command::new("wpr")
-            .args(&[
-                "-start",
-                &profile_arg,
-                "-filemode",
-                "-instancename",
-                &self.session_name,
-            ])
+
+        let mut args = vec!["-start".to_string(), profile_arg];
+        match self.config.mode {
+            WprLoggingMode::File => args.push("-filemode".to_string()),
+            // Omitting `-filemode` starts WPR with a circular in-memory
+            // buffer instead of writing straight to disk.
+            WprLoggingMode::Memory => {}
+        }
+        args.push("-instancename".to_string());
+        args.push(self.session_name.clone());
+
+        let output = std::process::Command::new(&self.wpr_path)
+            .args(&args)
             .output()?;
 
         if !output.status.success() {
@@ -94,8 +174,19 @@ impl WprSession {
         Ok(())
     }
 
-    /// Stop WPR tracing session and save ETL file
+    /// Stop the WPR session and save the ETL file. Equivalent to
+    /// `stop_if(true)`.
     pub fn stop(&self) -> anyhow::Result<Option<PathBuf>> {
+        self.stop_if(true)
+    }
+
+    /// Stop the WPR session. If `keep` is true, materializes the ETL file
+    /// (flushing the in-memory buffer to disk, in [`WprLoggingMode::Memory`]);
+    /// otherwise the session is cancelled and no ETL is written. In
+    /// `Memory` mode, callers pass `keep` based on whether the test failed,
+    /// so CI only keeps those traces for failing runs and avoids gigabytes
+    /// of passing-run ETLs; in `File` mode, they always pass `true`.
+    pub fn stop_if(&self, keep: bool) -> anyhow::Result<Option<PathBuf>> {
         if !self.config.enabled {
             return Ok(None);
         }
@@ -104,13 +195,27 @@ impl WprSession {
             return Ok(None);
         }
 
+        if !keep {
+            log::info!("Cancelling WPR session: {}", self.session_name);
+            let output = std::process::Command::new(&self.wpr_path)
+                .args(&["-cancel", "-instancename", &self.session_name])
+                .output()?;
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                log::warn!("Failed to cancel WPR session: {}", stderr);
+            }
+
+            return Ok(None);
+        }
+
         log::info!("Stopping WPR session: {}", self.session_name);
 
-        let output = std::process::Command::new("wpr")
+        let output = std::process::Command::new(&self.wpr_path)
             .args(&[
                 "-stop",
                 &self.output_file.to_string_lossy(),
-                "-instancename", 
+                "-instancename",
                 &self.session_name,
             ])
             .output()?;
@@ -131,83 +236,412 @@ impl WprSession {
     }
 
     fn get_embedded_profile_path(&self) -> anyhow::Result<String> {
-        // Create a temporary WPR profile file with OpenVMM/OpenHCL/Hyper-V specific providers
-        let temp_dir = std::env::temp_dir();
-        let profile_path = temp_dir.join("openvmm_wpr_profile.wprp");
-        
-        let profile_content = r#"<?xml version="1.0" encoding="utf-8"?>
+        WprProfileBuilder::detect().build()
+    }
+}
+
+/// The virtualization backend actually rooting this host, as distinguished
+/// from Hyper-V merely being enabled on a host it isn't backing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VirtStack {
+    /// The Microsoft hypervisor is the running root partition.
+    HyperV,
+    /// No Microsoft hypervisor is rooting this host (bare metal, WHP
+    /// without the Hyper-V role, or a non-Microsoft hypervisor).
+    OpenVmm,
+}
+
+impl VirtStack {
+    /// Probes CPUID hypervisor-present/vendor bits to tell a real
+    /// Hyper-V-backed VM from host-only Hyper-V artifacts.
+    fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            // SAFETY: CPUID is always safe to execute; it just reads CPU
+            // identification state.
+            let leaf1 = unsafe { std::arch::x86_64::__cpuid(1) };
+            let hypervisor_present = leaf1.ecx & (1 << 31) != 0;
+            if !hypervisor_present {
+                return VirtStack::OpenVmm;
+            }
+
+            // SAFETY: same as above.
+            let leaf0x4000_0000 = unsafe { std::arch::x86_64::__cpuid(0x4000_0000) };
+            let mut vendor = [0u8; 12];
+            vendor[0..4].copy_from_slice(&leaf0x4000_0000.ebx.to_le_bytes());
+            vendor[4..8].copy_from_slice(&leaf0x4000_0000.ecx.to_le_bytes());
+            vendor[8..12].copy_from_slice(&leaf0x4000_0000.edx.to_le_bytes());
+
+            if &vendor == b"Microsoft Hv" {
+                VirtStack::HyperV
+            } else {
+                VirtStack::OpenVmm
+            }
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            VirtStack::OpenVmm
+        }
+    }
+}
+
+/// Builds a WPR profile XML blob from the provider set relevant to the
+/// virtualization backend actually running on this host, instead of
+/// hard-coding Hyper-V providers that are absent (or misleading) when
+/// OpenVMM runs on WHP without the Hyper-V role.
+pub struct WprProfileBuilder {
+    virt_stack: VirtStack,
+    extra_providers: Vec<(String, u64, u8)>,
+}
+
+/// Escape the characters that are special in XML attribute/element text, so
+/// that a provider name never closes the tag or attribute it's embedded in.
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl WprProfileBuilder {
+    /// Create a builder for the virtualization backend actually rooting
+    /// this host.
+    pub fn detect() -> Self {
+        Self {
+            virt_stack: VirtStack::detect(),
+            extra_providers: Vec::new(),
+        }
+    }
+
+    /// Append a custom ETW provider (name, keyword mask, level) to the
+    /// profile, in addition to whatever the detected backend contributes.
+    pub fn with_provider(mut self, provider_name: &str, keyword_mask: u64, level: u8) -> Self {
+        self.extra_providers
+            .push((provider_name.to_string(), keyword_mask, level));
+        self
+    }
+
+    /// Render the profile to a temporary `.wprp` file and return its path.
+    pub fn build(&self) -> anyhow::Result<String> {
+        let (system_keywords, providers): (&[&str], Vec<&str>) = match self.virt_stack {
+            VirtStack::HyperV => (
+                &[
+                    "ProcessThread",
+                    "Loader",
+                    "CSwitch",
+                    "Interrupt",
+                    "DPC",
+                    "SampledProfile",
+                    "VirtualAlloc",
+                    "Memory",
+                    "HypervisorKernel",
+                    "HypervisorUser",
+                ],
+                vec![
+                    "Microsoft-Windows-Hyper-V-Hypervisor",
+                    "Microsoft-Windows-Hyper-V-VID",
+                    "Microsoft-Windows-Hyper-V-VmsIf",
+                ],
+            ),
+            VirtStack::OpenVmm => (
+                &[
+                    "ProcessThread",
+                    "Loader",
+                    "CSwitch",
+                    "Interrupt",
+                    "DPC",
+                    "SampledProfile",
+                    "VirtualAlloc",
+                    "Memory",
+                ],
+                vec!["Microsoft-OpenVMM-OpenHCL"],
+            ),
+        };
+
+        let mut event_providers_xml = String::new();
+        let mut event_provider_ids_xml = String::new();
+        for provider in &providers {
+            let provider = xml_escape(provider);
+            event_providers_xml.push_str(&format!(
+                "    <EventProvider Id=\"{provider}\" Name=\"{provider}\">\n      <Keywords>\n        <Keyword Value=\"0xFFFFFFFF\"/>\n      </Keywords>\n    </EventProvider>\n"
+            ));
+            event_provider_ids_xml
+                .push_str(&format!("            <EventProviderId Value=\"{provider}\"/>\n"));
+        }
+        for (name, keyword_mask, level) in &self.extra_providers {
+            let name = xml_escape(name);
+            event_providers_xml.push_str(&format!(
+                "    <EventProvider Id=\"{name}\" Name=\"{name}\">\n      <Keywords>\n        <Keyword Value=\"0x{keyword_mask:X}\"/>\n      </Keywords>\n      <Level Value=\"{level}\"/>\n    </EventProvider>\n"
+            ));
+            event_provider_ids_xml
+                .push_str(&format!("            <EventProviderId Value=\"{name}\"/>\n"));
+        }
+
+        let system_keywords_xml: String = system_keywords
+            .iter()
+            .map(|k| format!("        <Keyword Value=\"{k}\"/>\n"))
+            .collect();
+
+        let description = match self.virt_stack {
+            VirtStack::HyperV => "OpenVMM/Hyper-V Virtualization Stack Trace",
+            VirtStack::OpenVmm => "OpenVMM/OpenHCL Virtualization Stack Trace",
+        };
+
+        let profile_content = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
 <WindowsPerformanceRecorder Version="1.0" Author="OpenVMM" Team="OpenVMM">
   <Profiles>
     <SystemCollector Id="SystemCollector_OpenVMM" Name="NT Kernel Logger">
       <BufferSize Value="1024"/>
       <Buffers Value="100"/>
     </SystemCollector>
-    
+
     <EventCollector Id="EventCollector_OpenVMM" Name="OpenVMM Event Collector">
       <BufferSize Value="1024"/>
       <Buffers Value="100"/>
     </EventCollector>
-    
+
     <SystemProvider Id="SystemProvider_OpenVMM">
       <Keywords>
-        <Keyword Value="ProcessThread"/>
-        <Keyword Value="Loader"/>
-        <Keyword Value="CSwitch"/>
-        <Keyword Value="Interrupt"/>
-        <Keyword Value="DPC"/>
-        <Keyword Value="SampledProfile"/>
-        <Keyword Value="VirtualAlloc"/>
-        <Keyword Value="Memory"/>
-        <Keyword Value="HypervisorKernel"/>
-        <Keyword Value="HypervisorUser"/>
-      </Keywords>
+{system_keywords_xml}      </Keywords>
       <Stacks>
         <Stack Value="CSwitch"/>
         <Stack Value="ReadyThread"/>
         <Stack Value="VirtualAlloc"/>
       </Stacks>
     </SystemProvider>
-    
-    <EventProvider Id="Microsoft-Windows-Hyper-V-VmsIf" Name="Microsoft-Windows-Hyper-V-VmsIf">
-      <Keywords>
-        <Keyword Value="0xFFFFFFFF"/>
-      </Keywords>
-    </EventProvider>
-    
-    <EventProvider Id="Microsoft-Windows-Hyper-V-Hypervisor" Name="Microsoft-Windows-Hyper-V-Hypervisor">
-      <Keywords>
-        <Keyword Value="0xFFFFFFFF"/>
-      </Keywords>
-    </EventProvider>
-    
-    <EventProvider Id="Microsoft-Windows-Hyper-V-VID" Name="Microsoft-Windows-Hyper-V-VID">
-      <Keywords>
-        <Keyword Value="0xFFFFFFFF"/>
-      </Keywords>
-    </EventProvider>
-    
-    <Profile Id="OpenVMM_VirtStack.Verbose.File" Name="OpenVMM_VirtStack" Description="OpenVMM/OpenHCL/Hyper-V Virtualization Stack Trace" LoggingMode="File" DetailLevel="Verbose">
+
+{event_providers_xml}
+    <Profile Id="OpenVMM_VirtStack.Verbose.File" Name="OpenVMM_VirtStack" Description="{description}" LoggingMode="File" DetailLevel="Verbose">
       <Collectors>
         <SystemCollectorId Value="SystemCollector_OpenVMM">
           <SystemProviderId Value="SystemProvider_OpenVMM"/>
         </SystemCollectorId>
         <EventCollectorId Value="EventCollector_OpenVMM">
           <EventProviders>
-            <EventProviderId Value="Microsoft-Windows-Hyper-V-VmsIf"/>
-            <EventProviderId Value="Microsoft-Windows-Hyper-V-Hypervisor"/>
-            <EventProviderId Value="Microsoft-Windows-Hyper-V-VID"/>
-          </EventProviders>
+{event_provider_ids_xml}          </EventProviders>
         </EventCollectorId>
       </Collectors>
     </Profile>
   </Profiles>
-</WindowsPerformanceRecorder>"#;
+</WindowsPerformanceRecorder>"#
+        );
 
+        let temp_dir = std::env::temp_dir();
+        let profile_path = temp_dir.join("openvmm_wpr_profile.wprp");
         std::fs::write(&profile_path, profile_content)?;
         Ok(profile_path.to_string_lossy().to_string())
     }
 }
 
+impl TraceSession for WprSession {
+    fn start(&self) -> anyhow::Result<()> {
+        WprSession::start(self)
+    }
+
+    fn stop_if(&self, keep: bool) -> anyhow::Result<Option<PathBuf>> {
+        WprSession::stop_if(self, keep)
+    }
+}
+
+/// Linux tracing session, driving a system-wide (`-a`) `perf record` with
+/// call graphs, collecting a `perf.data`-shaped artifact. It does not target
+/// the OpenVMM worker process specifically, and it does not use LTTng. Mirrors the Windows `WprSession` start/stop
+/// lifecycle via [`TraceSession`].
+pub struct PerfSession {
+    session_name: String,
+    output_file: PathBuf,
+    config: WprConfig,
+    child: std::sync::Mutex<Option<std::process::Child>>,
+}
+
+impl PerfSession {
+    /// Create a new Linux kernel trace session
+    pub fn new(session_name: String, config: WprConfig) -> Self {
+        let output_file = config.output_dir.join(format!("{}.perfetto", session_name));
+        Self {
+            session_name,
+            output_file,
+            config,
+            child: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Start the kernel trace session
+    pub fn start(&self) -> anyhow::Result<()> {
+        if !self.config.enabled || cfg!(windows) {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.output_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        log::info!("Starting Linux kernel trace session: {}", self.session_name);
+
+        let child = std::process::Command::new("perf")
+            .args([
+                "record",
+                "-o",
+                &self.output_file.to_string_lossy(),
+                "-a",
+                "-g",
+            ])
+            .spawn()?;
+
+        *self.child.lock().unwrap() = Some(child);
+
+        log::debug!("Linux kernel trace session started successfully");
+        Ok(())
+    }
+
+    /// Stop the kernel trace session and save the trace file. Equivalent to
+    /// `stop_if(true)`.
+    pub fn stop(&self) -> anyhow::Result<Option<PathBuf>> {
+        self.stop_if(true)
+    }
+
+    /// Stop the kernel trace session. If `keep` is true, the trace file is
+    /// kept; otherwise `perf`'s output is discarded so a passing run doesn't
+    /// leave a multi-gigabyte `perf.data` artifact behind.
+    pub fn stop_if(&self, keep: bool) -> anyhow::Result<Option<PathBuf>> {
+        if !self.config.enabled || cfg!(windows) {
+            return Ok(None);
+        }
+
+        log::info!("Stopping Linux kernel trace session: {}", self.session_name);
+
+        let Some(mut child) = self.child.lock().unwrap().take() else {
+            return Ok(None);
+        };
+
+        // `perf record` flushes its output when it receives SIGINT/SIGTERM.
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+        }
+        child.wait()?;
+
+        if !keep {
+            log::info!("Discarding Linux kernel trace: {}", self.session_name);
+            let _ = std::fs::remove_file(&self.output_file);
+            return Ok(None);
+        }
+
+        if self.output_file.exists() {
+            log::info!(
+                "Linux kernel trace saved to: {}",
+                self.output_file.display()
+            );
+            Ok(Some(self.output_file.clone()))
+        } else {
+            log::warn!("Linux kernel trace file not found after stopping session");
+            Ok(None)
+        }
+    }
+}
+
+impl TraceSession for PerfSession {
+    fn start(&self) -> anyhow::Result<()> {
+        PerfSession::start(self)
+    }
+
+    fn stop_if(&self, keep: bool) -> anyhow::Result<Option<PathBuf>> {
+        PerfSession::stop_if(self, keep)
+    }
+}
+
+/// Locate `wpr.exe` the way MSVC toolchain discovery locates tools: through
+/// the Windows Kits registry, rather than assuming it's on `PATH`.
+///
+/// Checks, in order: `OPENVMM_WPR_PATH`, the `WindowsSdkDir` env var, and the
+/// `KitsRoot10`/`KitsRoot81` values under
+/// `HKLM\SOFTWARE\Microsoft\Windows Kits\Installed Roots`. Returns an error
+/// listing everywhere it looked if `wpr.exe` isn't found under any of them.
+fn resolve_wpr_path() -> anyhow::Result<PathBuf> {
+    let mut searched = Vec::new();
+
+    if let Ok(path) = std::env::var("OPENVMM_WPR_PATH") {
+        let path = PathBuf::from(path);
+        if path.is_file() {
+            return Ok(path);
+        }
+        searched.push(path);
+    }
+
+    let mut sdk_roots = Vec::new();
+    if let Ok(dir) = std::env::var("WindowsSdkDir") {
+        sdk_roots.push(PathBuf::from(dir));
+    }
+    for value in ["KitsRoot10", "KitsRoot81"] {
+        if let Some(root) = read_kits_root_registry_value(value) {
+            sdk_roots.push(root);
+        }
+    }
+
+    for root in sdk_roots {
+        let candidate = root.join("Windows Performance Toolkit").join("wpr.exe");
+        if candidate.is_file() {
+            return Ok(candidate);
+        }
+        searched.push(candidate);
+    }
+
+    anyhow::bail!(
+        "could not locate wpr.exe; searched: {}",
+        searched
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Reads a `KitsRootNN` value out of
+/// `HKLM\SOFTWARE\Microsoft\Windows Kits\Installed Roots` via `reg.exe
+/// query`, since that's available without pulling in a registry crate.
+fn read_kits_root_registry_value(value_name: &str) -> Option<PathBuf> {
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SOFTWARE\Microsoft\Windows Kits\Installed Roots",
+            "/v",
+            value_name,
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().find(|l| l.trim_start().starts_with(value_name))?;
+    let path = line.rsplit("REG_SZ").next()?.trim();
+    if path.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(path))
+    }
+}
+
+/// Get the trace backend to use, honoring `OPENVMM_TRACE_BACKEND` and
+/// falling back to auto-detection by target OS when unset.
+pub fn get_trace_backend_from_env() -> TraceBackend {
+    match std::env::var("OPENVMM_TRACE_BACKEND").as_deref() {
+        Ok("wpr") => TraceBackend::Wpr,
+        Ok("linux") => TraceBackend::LinuxKernelTrace,
+        _ => TraceBackend::detect(),
+    }
+}
+
 /// Get WPR configuration from environment variables
 pub fn get_wpr_config_from_env() -> WprConfig {
     let enabled = std::env::var("OPENVMM_WPR_ENABLED")
@@ -228,10 +662,16 @@ pub fn get_wpr_config_from_env() -> WprConfig {
         .map(PathBuf::from)
         .unwrap_or_else(|_| std::env::temp_dir().join("openvmm_wpr_traces"));
 
+    let mode = match std::env::var("OPENVMM_WPR_MODE").as_deref() {
+        Ok("memory") => WprLoggingMode::Memory,
+        _ => WprLoggingMode::File,
+    };
+
     WprConfig {
         enabled,
         profile,
         output_dir,
+        mode,
     }
 }
 
@@ -281,6 +721,7 @@ mod tests {
             enabled: true,
             profile: WprProfile::Embedded,
             output_dir: PathBuf::from("/tmp/traces"),
+            mode: WprLoggingMode::File,
         };
 
         let session = WprSession::new("test_session".to_string(), config);
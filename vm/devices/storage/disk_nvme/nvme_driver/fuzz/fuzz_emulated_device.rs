@@ -4,6 +4,8 @@
 //! A shim layer to fuzz responses from an emulated device.
 use std::collections::HashMap;
 use std::iter::Map;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use crate::arbitrary_data;
 
@@ -17,8 +19,10 @@ use user_driver::emulated::EmulatedDevice;
 use user_driver::emulated::EmulatedDmaAllocator;
 use user_driver::emulated::Mapping;
 use user_driver::interrupt::DeviceInterrupt;
+use user_driver::memory::MemoryBlock;
 use user_driver::DeviceBacking;
 use user_driver::DeviceRegisterIo;
+use user_driver::DmaClient;
 
 /// An EmulatedDevice fuzzer that requires a working EmulatedDevice backend.
 #[derive(Inspect)]
@@ -26,10 +30,111 @@ pub struct FuzzEmulatedDevice<T: PciConfigSpace + MmioIntercept + InspectMut> {
     device: EmulatedDevice<T>,
     #[inspect(skip)]
     bars: HashMap<u8, <FuzzEmulatedDevice<T> as DeviceBacking>::Registers>,
+    #[inspect(skip)]
+    interrupts: Arc<Mutex<HashMap<u32, DeviceInterrupt>>>,
+    #[inspect(skip)]
+    dma_buffers: Arc<Mutex<Vec<MemoryBlock>>>,
 }
 
 pub struct FuzzMapping<T> {
     device_bar: Mapping<T>,
+    /// Shared with `FuzzEmulatedDevice::interrupts`, so every register
+    /// read on this bar can opportunistically fire a mapped MSI-X vector
+    /// out-of-band -- this is the actual hot path the driver polls from, so
+    /// spurious interrupts land without a separate pump method the fuzz
+    /// loop would have to remember to call.
+    interrupts: Arc<Mutex<HashMap<u32, DeviceInterrupt>>>,
+    /// Shared with `FuzzEmulatedDevice::dma_buffers` for the same reason:
+    /// the driver notices a completion by polling a register (its doorbell
+    /// or CSTS), so that's also the right moment to reroll DMA buffer
+    /// contents, not the moment the buffer was allocated.
+    dma_buffers: Arc<Mutex<Vec<MemoryBlock>>>,
+}
+
+impl<T> FuzzMapping<T> {
+    /// Arbitrarily fire one of the currently-mapped MSI-X vectors, possibly
+    /// several times in a row (a burst real hardware should never produce).
+    /// Interleaves spurious/burst interrupts with whatever register state
+    /// the driver is actually polling for.
+    fn fuzz_pump_interrupts(&self) {
+        let interrupts = self.interrupts.lock().unwrap();
+        if interrupts.is_empty() {
+            return;
+        }
+
+        let burst = match arbitrary_data::<u8>() {
+            Ok(b) => (b % 5) as u32,
+            Err(_) => return,
+        };
+        if burst == 0 {
+            return;
+        }
+
+        let idx = match arbitrary_data::<u8>() {
+            Ok(i) => i as usize % interrupts.len(),
+            Err(_) => return,
+        };
+        if let Some(interrupt) = interrupts.values().nth(idx) {
+            for _ in 0..burst {
+                interrupt.deliver();
+            }
+        }
+    }
+
+    /// Arbitrarily reroll the contents of one of the outstanding DMA
+    /// buffers. Run right as the driver polls a register for completion,
+    /// so a real completion entry the device already wrote can still get
+    /// clobbered before the driver reads it back -- the thing that matters
+    /// for fuzzing, unlike corrupting the buffer once at allocation time.
+    fn fuzz_corrupt_dma(&self) {
+        let buffers = self.dma_buffers.lock().unwrap();
+        if buffers.is_empty() {
+            return;
+        }
+
+        if !matches!(arbitrary_data::<bool>(), Ok(true)) {
+            return;
+        }
+
+        let idx = match arbitrary_data::<u8>() {
+            Ok(i) => i as usize % buffers.len(),
+            Err(_) => return,
+        };
+        for byte in buffers[idx].as_slice() {
+            if let Ok(true) = arbitrary_data::<bool>() {
+                if let Ok(data) = arbitrary_data::<u8>() {
+                    byte.store(data, std::sync::atomic::Ordering::SeqCst);
+                }
+            }
+        }
+    }
+}
+
+/// A `DmaClient` fuzzer wrapping the real `EmulatedDmaAllocator`. Allocation
+/// bookkeeping is left untouched; buffers are instead handed to
+/// `FuzzMapping`, which may reroll their contents on every register read
+/// (see `fuzz_corrupt_dma`), so corruption lands at read-back time instead
+/// of being overwritten by the device's own completion write. This
+/// simulates a malicious device DMA'ing garbage -- bad CQ phase bits,
+/// bogus status codes, garbage PRP list entries -- into driver-visible
+/// memory that the driver must still validate.
+pub struct FuzzDmaAllocator {
+    inner: EmulatedDmaAllocator,
+    dma_buffers: Arc<Mutex<Vec<MemoryBlock>>>,
+}
+
+impl DmaClient for FuzzDmaAllocator {
+    fn allocate_dma_buffer(&self, len: usize) -> anyhow::Result<MemoryBlock> {
+        let mem = self.inner.allocate_dma_buffer(len)?;
+        self.dma_buffers.lock().unwrap().push(mem.clone());
+        Ok(mem)
+    }
+
+    fn attach_dma_buffer(&self, len: usize, base_pfn: u64) -> anyhow::Result<MemoryBlock> {
+        let mem = self.inner.attach_dma_buffer(len, base_pfn)?;
+        self.dma_buffers.lock().unwrap().push(mem.clone());
+        Ok(mem)
+    }
 }
 
 impl<T: PciConfigSpace + MmioIntercept + InspectMut> FuzzEmulatedDevice<T> {
@@ -38,12 +143,17 @@ impl<T: PciConfigSpace + MmioIntercept + InspectMut> FuzzEmulatedDevice<T> {
         Self {
             device: EmulatedDevice::new(device, msi_set, shared_mem),
             bars: HashMap::new(),
+            interrupts: Arc::new(Mutex::new(HashMap::new())),
+            dma_buffers: Arc::new(Mutex::new(Vec::new())),
         }
     }
 }
 
 impl<T: PciConfigSpace + MmioIntercept + InspectMut> DeviceRegisterIo for FuzzMapping<T> {
     fn read_u32(&self, offset: usize) -> u32 {
+        self.fuzz_pump_interrupts();
+        self.fuzz_corrupt_dma();
+
         if let Ok(true) = arbitrary_data::<bool>() {
             if let Ok(data) = arbitrary_data::<u32>() {
                 return data;
@@ -54,6 +164,9 @@ impl<T: PciConfigSpace + MmioIntercept + InspectMut> DeviceRegisterIo for FuzzMa
     }
 
     fn read_u64(&self, offset: usize) -> u64 {
+        self.fuzz_pump_interrupts();
+        self.fuzz_corrupt_dma();
+
         if let Ok(true) = arbitrary_data::<bool>() {
             if let Ok(data) = arbitrary_data::<u64>() {
                 return data;
@@ -75,7 +188,7 @@ impl<T: PciConfigSpace + MmioIntercept + InspectMut> DeviceRegisterIo for FuzzMa
 /// Implementation for DeviceBacking trait.
 impl<T: 'static + Send + InspectMut + MmioIntercept> DeviceBacking for FuzzEmulatedDevice<T> {
     type Registers = Mapping<T>;
-    type DmaAllocator = EmulatedDmaAllocator;
+    type DmaAllocator = FuzzDmaAllocator;
 
     fn id(&self) -> &str {
         self.device.id()
@@ -84,14 +197,21 @@ impl<T: 'static + Send + InspectMut + MmioIntercept> DeviceBacking for FuzzEmula
     fn map_bar(&mut self, n: u8) -> anyhow::Result<Self::Registers> {
         let device_bar = self.device.map_bar(n)?;
 
-        let fuzz_mapping = FuzzMapping { device_bar };
+        let fuzz_mapping = FuzzMapping {
+            device_bar,
+            interrupts: self.interrupts.clone(),
+            dma_buffers: self.dma_buffers.clone(),
+        };
         self.bars.insert(n, fuzz_mapping);
 
         Ok(fuzz_mapping)
     }
 
     fn host_allocator(&self) -> Self::DmaAllocator {
-        self.device.host_allocator()
+        FuzzDmaAllocator {
+            inner: self.device.host_allocator(),
+            dma_buffers: self.dma_buffers.clone(),
+        }
     }
 
 
@@ -110,6 +230,11 @@ impl<T: 'static + Send + InspectMut + MmioIntercept> DeviceBacking for FuzzEmula
     }
 
     fn map_interrupt(&mut self, msix: u32, _cpu: u32) -> anyhow::Result<DeviceInterrupt> {
-        self.device.map_interrupt(msix, _cpu)
+        let interrupt = self.device.map_interrupt(msix, _cpu)?;
+        // Keep our own handle on the interrupt, shared with every
+        // `FuzzMapping` we've handed out, so a register read on any bar
+        // can signal it independent of the driver.
+        self.interrupts.lock().unwrap().insert(msix, interrupt.clone());
+        Ok(interrupt)
     }
 }
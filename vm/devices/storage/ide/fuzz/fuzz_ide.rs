@@ -0,0 +1,128 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg_attr(all(target_os = "linux", target_env = "gnu"), no_main)]
+
+use arbitrary::Arbitrary;
+use arbitrary::Unstructured;
+use chipset_device::io::IoIntercept;
+use guestmem::GuestMemory;
+use ide::IdeController;
+use ide::IdeDeviceConfig;
+use libfuzzer_sys::fuzz_target;
+use pal_async::DefaultPool;
+use std::sync::Arc;
+use xtask_fuzz::fuzz_eprintln;
+
+/// A single Physical Region Descriptor table entry, as programmed by the
+/// guest for bus-master DMA. The hardware format is an 8-byte little-endian
+/// tuple: a 32-bit base address (bit 0 reserved), followed by a 16-bit byte
+/// count (0 means 64 KiB) and the end-of-table flag in the top bit of the
+/// second dword.
+#[derive(Arbitrary, Clone, Copy)]
+struct PrdEntry {
+    base_gpa: u32,
+    byte_count: u16,
+    eot: bool,
+}
+
+impl PrdEntry {
+    fn to_bytes(self) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes[0..4].copy_from_slice(&(self.base_gpa & !1).to_le_bytes());
+        let count_and_eot = self.byte_count as u32 | if self.eot { 0x8000_0000 } else { 0 };
+        bytes[4..8].copy_from_slice(&count_and_eot.to_le_bytes());
+        bytes
+    }
+}
+
+#[derive(Arbitrary)]
+enum IdeFuzzAction {
+    /// Program an arbitrary PRD table at an arbitrary guest address and
+    /// point the bus-master's PRD table pointer register at it.
+    ProgramPrdTable { table_gpa: u32, entries: Vec<PrdEntry> },
+    /// Program the legacy task-file registers (LBA/sector count) and issue
+    /// a PIO or DMA ATA command.
+    IssueCommand {
+        lba: u32,
+        sector_count: u8,
+        command: u8,
+    },
+    /// Toggle the bus-master start/stop bit and direction, and read back
+    /// the bus-master status register.
+    ToggleBusMaster { start: bool, read_not_write: bool },
+}
+
+const TASK_FILE_BASE: u16 = 0x1f0;
+const BUS_MASTER_BASE: u16 = 0xc000;
+
+fn do_fuzz(u: &mut Unstructured<'_>) -> Result<(), anyhow::Error> {
+    fuzz_eprintln!("repro-ing test case...");
+
+    DefaultPool::run_with(|_driver| async move {
+        let guest_mem_pages = u.int_in_range(1..=256)?;
+        let guest_mem = GuestMemory::allocate(guest_mem_pages * 4096);
+
+        let disk_len_sectors = u.int_in_range(1..=1048576)?; // up to 512mb in 512 byte sectors
+        let disk = disklayer_ram::ram_disk(disk_len_sectors * 512, false).unwrap();
+
+        let mut controller = IdeController::new(IdeDeviceConfig {
+            primary: Some(Arc::new(disk)),
+            secondary: None,
+        });
+
+        while !u.is_empty() {
+            match u.arbitrary::<IdeFuzzAction>()? {
+                IdeFuzzAction::ProgramPrdTable { table_gpa, entries } => {
+                    // Cap the table so we don't spend the whole fuzz budget
+                    // writing one enormous descriptor list.
+                    let entries: Vec<_> = entries.into_iter().take(64).collect();
+                    let mut offset = 0u64;
+                    for entry in &entries {
+                        let _ = guest_mem.write_at(table_gpa as u64 + offset, &entry.to_bytes());
+                        offset += 8;
+                    }
+
+                    let _ = controller.io_write(BUS_MASTER_BASE + 0x4, &table_gpa.to_le_bytes());
+                }
+                IdeFuzzAction::IssueCommand {
+                    lba,
+                    sector_count,
+                    command,
+                } => {
+                    let _ = controller.io_write(TASK_FILE_BASE + 2, &[sector_count]);
+                    let _ = controller.io_write(TASK_FILE_BASE + 3, &[lba as u8]);
+                    let _ = controller.io_write(TASK_FILE_BASE + 4, &[(lba >> 8) as u8]);
+                    let _ = controller.io_write(TASK_FILE_BASE + 5, &[(lba >> 16) as u8]);
+                    let _ = controller.io_write(TASK_FILE_BASE + 7, &[command]);
+
+                    let mut status = [0u8];
+                    let _ = controller.io_read(TASK_FILE_BASE + 7, &mut status);
+                }
+                IdeFuzzAction::ToggleBusMaster {
+                    start,
+                    read_not_write,
+                } => {
+                    let command = (start as u8) | ((!read_not_write as u8) << 3);
+                    let _ = controller.io_write(BUS_MASTER_BASE, &[command]);
+
+                    let mut status = [0u8];
+                    let _ = controller.io_read(BUS_MASTER_BASE + 0x2, &mut status);
+                }
+            }
+        }
+
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fuzz_target!(|input: &[u8]| -> libfuzzer_sys::Corpus {
+    xtask_fuzz::init_tracing_if_repro();
+
+    let _ = do_fuzz(&mut Unstructured::new(input));
+
+    // Always keep the corpus, since errors are a reasonable outcome.
+    libfuzzer_sys::Corpus::Keep
+});
@@ -27,6 +27,11 @@ use zerocopy::AsBytes;
 pub enum StovspFuzzAction {
     SendDataPacket,
     SendRawPacket,
+    /// Tear down the guest channel and worker and re-establish them, the
+    /// way a live VM reset revokes and re-creates queues. The controller
+    /// itself is not torn down, so it must clean up any transactions that
+    /// were outstanding on the old channel.
+    ResetChannel,
 }
 
 #[derive(Arbitrary)]
@@ -64,8 +69,8 @@ fn do_fuzz(u: &mut Unstructured<'_>) -> Result<(), anyhow::Error> {
         );
         controller.attach(u.arbitrary()?, ScsiControllerDisk::new(Arc::new(disk)))?;
 
-        let _test_worker = TestWorker::start(
-            controller,
+        let mut _test_worker = TestWorker::start(
+            controller.clone(),
             driver.clone(),
             test_guest_mem.clone(),
             host,
@@ -84,6 +89,36 @@ fn do_fuzz(u: &mut Unstructured<'_>) -> Result<(), anyhow::Error> {
         while !u.is_empty() {
             let action = u.arbitrary::<StovspFuzzAction>()?;
             match action {
+                StovspFuzzAction::ResetChannel => {
+                    // Drop and re-establish the guest channel/worker the way
+                    // a live VM reset re-wires the interrupt and queue event
+                    // plumbing, so the controller's cleanup of outstanding
+                    // `ScsiRequest`s is what gets exercised.
+                    let (host, guest_channel) = connected_async_channels(channel_count * 1024);
+                    let guest_queue = Queue::new(guest_channel).unwrap();
+
+                    _test_worker = TestWorker::start(
+                        controller.clone(),
+                        driver.clone(),
+                        test_guest_mem.clone(),
+                        host,
+                        None,
+                    );
+
+                    guest = TestGuest {
+                        queue: guest_queue,
+                        transaction_id: 0,
+                    };
+
+                    // Usually renegotiate right away; otherwise leave the
+                    // fresh channel un-negotiated, so later actions deliver
+                    // I/O (and any negotiation packets that arbitrary data
+                    // packets happen to carry) to a channel that was never
+                    // renegotiated.
+                    if u.ratio(9, 10)? {
+                        guest.perform_protocol_negotiation().await;
+                    }
+                }
                 StovspFuzzAction::SendDataPacket => {
                     let packet = u.arbitrary::<Packet>()?;
                     let _ = guest.send_data_packet_sync(&[packet.as_bytes()]).await;
@@ -5,18 +5,34 @@
 
 use petri::PetriVm;
 use petri::PetriVmmBackend;
+use std::collections::HashMap;
 
+#[derive(Clone, Copy)]
 pub(crate) struct ExpectedNvmeDeviceProperties {
     pub save_restore_supported: bool,
     pub qsize: u64,
     pub nvme_keepalive: bool,
 }
 
-/// Check that the NVMe driver state in the VM matches the expected properties.
-/// If `props` is `None`, then we skip validating the properties.
+/// Check that the NVMe driver state in the VM matches the expected
+/// properties, for the single default device. A thin wrapper over
+/// [`check_expected_nvme_driver_state_multi`] for existing single-device
+/// call sites that don't need to name a PCI id.
 pub(crate) async fn check_expected_nvme_driver_state<T: PetriVmmBackend>(
     vm: &PetriVm<T>,
     props: &Option<ExpectedNvmeDeviceProperties>,
+) -> Result<(), anyhow::Error> {
+    let expected_devices = HashMap::from([("718b:00:00.0", *props)]);
+    check_expected_nvme_driver_state_multi(vm, &expected_devices).await
+}
+
+/// Check that the NVMe driver state in the VM matches the expected
+/// properties, for every device in `expected_devices` (keyed by PCI id).
+/// If an entry's properties are `None`, then we skip validating that
+/// device's properties, but still require the device to be present.
+pub(crate) async fn check_expected_nvme_driver_state_multi<T: PetriVmmBackend>(
+    vm: &PetriVm<T>,
+    expected_devices: &HashMap<&str, Option<ExpectedNvmeDeviceProperties>>,
 ) -> Result<(), anyhow::Error> {
     let devices = vm.inspect_openhcl("vm/nvme/devices", None, None).await?;
     tracing::info!(devices = %devices.json(), "NVMe devices");
@@ -77,40 +93,54 @@ pub(crate) async fn check_expected_nvme_driver_state<T: PetriVmmBackend>(
     }
     */
 
-    // If just one device is returned, then this will be a `Value::Object`, where the
-    // key is the single PCI ID of the device.
-    //
-    // TODO (future PR): Fix this up with support for multiple devices when this code is used
-    // in more complicated tests.
-    let found_device_id = devices
-        .as_object()
-        .expect("devices object")
-        .keys()
-        .next()
-        .expect("device id");
+    let found_devices = devices.as_object().expect("devices object");
 
-    // The PCI id is generated from the VMBUS instance guid for vpci devices.
-    // See `PARAVISOR_BOOT_NVME_INSTANCE`.
-    assert_eq!(found_device_id, "718b:00:00.0");
-    if let Some(props) = props {
-        assert_eq!(
-            devices[found_device_id]["driver"]["driver"]["qsize"]
+    for (device_id, props) in expected_devices {
+        let device = found_devices
+            .get(*device_id)
+            .unwrap_or_else(|| panic!("device {device_id} not found"));
+
+        if let Some(props) = props {
+            assert_eq!(
+                device["driver"]["driver"]["qsize"]
+                    .as_u64()
+                    .expect("qsize"),
+                props.qsize,
+                "qsize mismatch for device {device_id}"
+            );
+            assert_eq!(
+                device["driver"]["driver"]["nvme_keepalive"]
+                    .as_bool()
+                    .expect("nvme_keepalive"),
+                props.nvme_keepalive,
+                "nvme_keepalive mismatch for device {device_id}"
+            );
+            assert_eq!(
+                device["save_restore_supported"]
+                    .as_bool()
+                    .expect("save_restore_supported"),
+                props.save_restore_supported,
+                "save_restore_supported mismatch for device {device_id}"
+            );
+
+            // Check the `io_issuers` map as a whole: at least one entry, and
+            // no more entries than `max_io_queues`. The individual entries
+            // are not inspected.
+            let max_io_queues = device["driver"]["driver"]["max_io_queues"]
                 .as_u64()
-                .expect("qsize"),
-            props.qsize
-        );
-        assert_eq!(
-            devices[found_device_id]["driver"]["driver"]["nvme_keepalive"]
-                .as_bool()
-                .expect("nvme_keepalive"),
-            props.nvme_keepalive
-        );
-        assert_eq!(
-            devices[found_device_id]["save_restore_supported"]
-                .as_bool()
-                .expect("save_restore_supported"),
-            props.save_restore_supported
-        );
+                .expect("max_io_queues");
+            let io_issuers = device["driver"]["driver"]["io_issuers"]
+                .as_object()
+                .expect("io_issuers object");
+            assert!(
+                !io_issuers.is_empty(),
+                "no io_issuers reported for device {device_id}"
+            );
+            assert!(
+                (io_issuers.len() as u64) <= max_io_queues,
+                "more io_issuers than max_io_queues for device {device_id}"
+            );
+        }
     }
 
     Ok(())